@@ -48,14 +48,16 @@
 //! ```
 
 
-extern crate bit_vec;
-
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-
-use bit_vec::BitVec;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
 
-// Struct used to hold a reference to a key
+// Struct used to hold a reference to a key. One stored in `map` always points into a
+// `ClockEntry`'s boxed `key`, never directly into `entries`, so moving or reallocating the
+// `Vec` that holds the entries (e.g. `set_capacity`, or `entries` growing past its initial
+// capacity) never invalidates it: the `Box<K>` moves, but the `K` it owns stays put on the
+// heap.
 struct KeyRef<K> {
     k: *const K,
 }
@@ -75,26 +77,54 @@ impl<K: PartialEq> PartialEq for KeyRef<K> {
 impl<K: Eq> Eq for KeyRef<K> {}
 
 struct ClockEntry<K, V> {
-    key: K,
+    // boxed so its address is stable even when `entries` itself moves; see `KeyRef`.
+    key: Box<K>,
     val: V,
+    created: Instant,
+    ttl: Option<Duration>,
+    weight: usize,
 }
 
 impl<K, V> ClockEntry<K, V> {
-    fn new(key: K, val: V) -> Self {
+    fn new(key: K, val: V, ttl: Option<Duration>, weight: usize) -> Self {
         ClockEntry {
-            key: key,
+            key: Box::new(key),
             val: val,
+            created: Instant::now(),
+            ttl: ttl,
+            weight: weight,
         }
     }
 }
 
+/// A trait for computing the weight of a key-value pair, letting a `ClockCache` bound the
+/// sum of weights of the entries it holds rather than a fixed number of entries.
+pub trait WeightScale<K, V> {
+    /// Return the weight of a key-value pair.
+    fn weight(&self, key: &K, val: &V) -> usize;
+}
+
+/// The default `WeightScale`, giving every entry a weight of `1` and so reproducing the
+/// cache's original one-slot-per-entry behavior.
+pub struct ZeroWeightScale;
+
+impl<K, V> WeightScale<K, V> for ZeroWeightScale {
+    fn weight(&self, _key: &K, _val: &V) -> usize {
+        1
+    }
+}
+
 /// A Clock Cache
-pub struct ClockCache<K, V> {
-    map: HashMap<KeyRef<K>, usize>,
+pub struct ClockCache<K, V, S = RandomState, W = ZeroWeightScale> {
+    map: HashMap<KeyRef<K>, usize, S>,
     entries: Vec<ClockEntry<K, V>>,
-    bits: BitVec,
+    counts: Vec<u8>,
     cap: usize,
     idx: usize,
+    ttl: Option<Duration>,
+    scale: W,
+    total_weight: usize,
+    max_count: u8,
 }
 
 impl<K: Hash + Eq, V> ClockCache<K, V> {
@@ -107,16 +137,194 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     /// let mut cache: ClockCache<isize, &str> = ClockCache::new(10);
     /// ```
     pub fn new(cap: usize) -> ClockCache<K, V> {
+        ClockCache::with_hasher(cap, RandomState::new())
+    }
+
+    /// Create a new ClockCache that holds at most `cap` items, each of which expires `ttl`
+    /// after it is inserted. An expired entry is treated as absent by `get`, `peek` and
+    /// `contains`, and its slot becomes immediately available for reuse.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use clock_cache::ClockCache;
+    /// let mut cache: ClockCache<isize, &str> =
+    ///     ClockCache::with_expiry_duration(10, Duration::from_secs(30));
+    /// ```
+    pub fn with_expiry_duration(cap: usize, ttl: Duration) -> ClockCache<K, V> {
+        let mut cache = ClockCache::new(cap);
+        cache.ttl = Some(ttl);
+        cache
+    }
+
+    /// Create a new ClockCache that holds at most `cap` items, using a generalized CLOCK
+    /// (GCLOCK) policy with a saturating reference counter bounded by `max_count` instead
+    /// of a single usage bit. An entry survives a clock sweep for as many passes as its
+    /// counter allows, so frequently accessed entries resist eviction by one-shot scans.
+    /// `max_count == 1` reproduces the cache's original plain CLOCK behavior exactly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache: ClockCache<isize, &str> = ClockCache::with_max_count(10, 3);
+    /// ```
+    pub fn with_max_count(cap: usize, max_count: u8) -> ClockCache<K, V> {
+        let mut cache = ClockCache::new(cap);
+        cache.max_count = max_count;
+        cache
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> ClockCache<K, V, S, ZeroWeightScale> {
+    /// Create a new ClockCache that holds at most `cap` items and uses the given hash
+    /// builder to hash keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use clock_cache::ClockCache;
+    /// let s = RandomState::new();
+    /// let mut cache: ClockCache<isize, &str, RandomState> = ClockCache::with_hasher(10, s);
+    /// ```
+    pub fn with_hasher(cap: usize, hash_builder: S) -> ClockCache<K, V, S, ZeroWeightScale> {
+        ClockCache::with_hasher_and_weight_scale(cap, hash_builder, ZeroWeightScale)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, W: WeightScale<K, V>> ClockCache<K, V, S, W> {
+    /// Create a new ClockCache that bounds the total weight of its entries (as computed by
+    /// `scale`) to at most `cap`, and uses the given hash builder to hash keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use clock_cache::{ClockCache, WeightScale};
+    ///
+    /// struct Len;
+    /// impl WeightScale<i32, String> for Len {
+    ///     fn weight(&self, _key: &i32, val: &String) -> usize {
+    ///         val.len()
+    ///     }
+    /// }
+    ///
+    /// let mut cache: ClockCache<i32, String, RandomState, Len> =
+    ///     ClockCache::with_hasher_and_weight_scale(10, RandomState::new(), Len);
+    /// ```
+    pub fn with_hasher_and_weight_scale(
+        cap: usize,
+        hash_builder: S,
+        scale: W,
+    ) -> ClockCache<K, V, S, W> {
         ClockCache {
-            map: HashMap::with_capacity(cap),
+            map: HashMap::with_capacity_and_hasher(cap, hash_builder),
             entries: Vec::with_capacity(cap),
-            bits: BitVec::from_fn(cap, |_| false),
+            counts: Vec::new(),
             cap: cap,
             idx: 0,
+            ttl: None,
+            scale: scale,
+            total_weight: 0,
+            max_count: 1,
+        }
+    }
+
+    /// Return `true` if the entry in slot `idx` has outlived its TTL (if any).
+    fn is_expired(&self, idx: usize) -> bool {
+        let entry = &self.entries[idx];
+        match entry.ttl {
+            Some(ttl) => entry.created.elapsed() >= ttl,
+            None => false,
         }
     }
 
-    /// Put a key-value pair into the cache. If the key already exists update its value.
+    /// Saturating-increment the reference count of the entry in slot `idx`, up to
+    /// `max_count`.
+    fn mark_used(&mut self, idx: usize) {
+        if self.counts[idx] < self.max_count {
+            self.counts[idx] += 1;
+        }
+    }
+
+    /// Swap-remove the entry at `idx`, fixing up `map`'s stored index for whichever live
+    /// entry (if any) the swap moved into `idx`, and keeping the clock hand in bounds.
+    /// Shared by `remove`, `remove_expired` and `evict_one` so none leaves a "zombie" slot
+    /// behind: every removal actually shrinks `entries`/`counts` rather than merely clearing a
+    /// marker.
+    fn swap_remove_slot(&mut self, idx: usize) -> ClockEntry<K, V> {
+        let last_idx = self.entries.len() - 1;
+        let moved_is_live = if idx != last_idx {
+            let moved_key = KeyRef { k: &*self.entries[last_idx].key };
+            let live = self.map
+                .get(&moved_key)
+                .map(|&mapped| mapped == last_idx)
+                .unwrap_or(false);
+            if live {
+                self.map.remove(&moved_key);
+            }
+            live
+        } else {
+            false
+        };
+
+        let removed = self.entries.swap_remove(idx);
+        self.counts.swap_remove(idx);
+
+        if moved_is_live {
+            let moved_key = KeyRef { k: &*self.entries[idx].key };
+            self.map.insert(moved_key, idx);
+        }
+
+        if self.entries.is_empty() {
+            self.idx = 0;
+        } else {
+            self.idx %= self.entries.len();
+        }
+
+        removed
+    }
+
+    /// Advance the clock hand until it lands on a slot that can be reclaimed -- one that is
+    /// expired, or whose reference count has decayed to zero -- then evict it immediately
+    /// (via `swap_remove_slot`, rather than leaving it behind as a zombie for a later pass to
+    /// find) and return the weight it freed.
+    ///
+    /// Every slot the hand passes over that isn't reclaimed has its counter decremented, so
+    /// each sweep strictly lowers the total of all counters and the loop is guaranteed to
+    /// terminate; because the reclaimed slot is actually removed, every call shrinks
+    /// `entries` by exactly one, so repeated calls are guaranteed to make progress too.
+    fn evict_one(&mut self) -> usize {
+        loop {
+            if self.is_expired(self.idx) {
+                break;
+            }
+            if self.counts[self.idx] > 0 {
+                self.counts[self.idx] -= 1;
+                self.idx = (self.idx + 1) % self.entries.len();
+                continue;
+            }
+            break;
+        }
+
+        let idx = self.idx;
+        let old_key = KeyRef { k: &*self.entries[idx].key };
+        // an expired entry may already have been unmapped by `get` and its key re-inserted
+        // into another slot since; only unmap the key if it still points at this slot
+        if self.map.get(&old_key) == Some(&idx) {
+            self.map.remove(&old_key);
+        }
+
+        let freed = self.entries[idx].weight;
+        self.total_weight -= freed;
+        self.swap_remove_slot(idx);
+        freed
+    }
+
+    /// Put a key-value pair into the cache. If the key already exists update its value. The
+    /// entry's weight is computed by the cache's `WeightScale`.
     ///
     /// # Example
     ///
@@ -130,49 +338,128 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     /// assert_eq!(cache.get(&2), Some(&"b"));
     /// ```
     pub fn put(&mut self, k: K, v: V) {
+        let ttl = self.ttl;
+        let weight = self.scale.weight(&k, &v);
+        let _ = self.put_entry(k, v, ttl, weight);
+    }
+
+    /// Put a key-value pair into the cache with a per-entry `ttl`, overriding the cache's
+    /// default expiry duration (if any) for this key alone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// cache.put_with_expiry(1, "a", Duration::from_secs(60));
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// ```
+    pub fn put_with_expiry(&mut self, k: K, v: V, ttl: Duration) {
+        let weight = self.scale.weight(&k, &v);
+        let _ = self.put_entry(k, v, Some(ttl), weight);
+    }
+
+    /// Put a key-value pair into the cache with an explicit `weight`, overriding the
+    /// cache's `WeightScale` for this key alone. Returns the rejected value in `Err` if
+    /// `weight` alone exceeds the cache's total capacity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(4);
+    ///
+    /// assert!(cache.put_with_weight(1, "a", 2).is_ok());
+    /// assert_eq!(cache.weight(), 2);
+    /// assert!(cache.put_with_weight(2, "b", 8).is_err());
+    /// ```
+    pub fn put_with_weight(&mut self, k: K, v: V, weight: usize) -> Result<(), V> {
+        let ttl = self.ttl;
+        self.put_entry(k, v, ttl, weight).map(|_| ())
+    }
+
+    fn put_entry(&mut self, k: K, v: V, ttl: Option<Duration>, weight: usize) -> Result<usize, V> {
+        if weight > self.cap {
+            return Err(v);
+        }
+
         // check if the key is already in the cache
         match self.map.get(&KeyRef { k: &k }) {
             Some(idx) => {
-                self.entries.get_mut(*idx).map(|entry| entry.val = v);
-                return;
-            }
-            None => (),
-        };
+                let idx = *idx;
+                let old_weight = self.entries[idx].weight;
 
-        let entry = if self.entries.len() < self.cap {
-            // if entries is not full yet, push a new entry onto the end
-            self.entries.push(ClockEntry::new(k, v));
-            self.entries.get_mut(self.idx).unwrap()
-        } else {
-            // if entries is full, find and use the first entry with its usage bit set to false
-            let mut usage_bit = self.bits.get(self.idx).unwrap();
+                // apply the new value up front so the eviction sweep below sees a fresh,
+                // unexpired entry here rather than stale ttl/created fields, and pin its
+                // reference count so the sweep can't reclaim the very slot we're growing
+                let entry = self.entries.get_mut(idx).unwrap();
+                entry.val = v;
+                entry.created = Instant::now();
+                entry.ttl = ttl;
+                entry.weight = weight;
+                let saved_count = self.counts[idx];
+                self.counts[idx] = u8::max_value();
+
+                // a larger weight can push the cache over `cap`; evict other entries,
+                // preferring expired or unreferenced ones, until growing this one fits
+                self.total_weight -= old_weight;
+                while self.total_weight + weight > self.cap {
+                    self.evict_one();
+                }
+                self.total_weight += weight;
 
-            while usage_bit {
-                self.bits.set(self.idx, false);
-                self.idx = (self.idx + 1) % self.cap;
-                usage_bit = self.bits.get(self.idx).unwrap();
+                // evicting another entry may have swap-removed it into this one's old slot
+                let idx = *self.map.get(&KeyRef { k: &k }).unwrap();
+                self.counts[idx] = saved_count;
+                self.mark_used(idx);
+                return Ok(idx);
             }
+            None => (),
+        };
 
-            self.bits.set(self.idx, true);
+        // evict entries, preferring expired or unreferenced ones, until enough weight has
+        // been freed for the new entry; each call to `evict_one` removes exactly one entry,
+        // so `entries.len()` stays bounded by `cap` even when a single bigger put has to
+        // evict several smaller ones
+        while self.total_weight + weight > self.cap {
+            self.evict_one();
+        }
 
-            let entry = self.entries.get_mut(self.idx).unwrap();
+        self.entries.push(ClockEntry::new(k, v, ttl, weight));
+        self.counts.push(0);
+        let entry_idx = self.entries.len() - 1;
 
-            let old_key = KeyRef { k: &entry.key };
-            self.map.remove(&old_key);
+        // clamp to `max_count` so a degenerate `with_max_count(cap, 0)` cache doesn't hand
+        // out a starting counter above its own configured ceiling
+        self.counts[entry_idx] = 1.min(self.max_count);
 
-            entry.key = k;
-            entry.val = v;
-            entry
-        };
+        let key = KeyRef { k: &*self.entries[entry_idx].key };
+        self.map.insert(key, entry_idx);
+        self.total_weight += weight;
 
-        let key = KeyRef { k: &entry.key };
-        self.map.insert(key, self.idx);
+        self.idx = (entry_idx + 1) % self.entries.len();
+        Ok(entry_idx)
+    }
 
-        self.idx = (self.idx + 1) % self.cap;
+    /// Return the total weight of the entries currently in the cache, as computed by the
+    /// cache's `WeightScale`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    /// cache.put(1, "a");
+    /// assert_eq!(cache.weight(), 1);
+    /// ```
+    pub fn weight(&self) -> usize {
+        self.total_weight
     }
 
     /// Return the value corresponding to the key in the cache or `None` if it is not
-    /// present in the cache. Update the key's usage bit if it exists.
+    /// present in the cache. Marks the key's entry as used if it exists.
     ///
     /// # Example
     ///
@@ -191,17 +478,101 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     /// ```
     pub fn get<'a>(&'a mut self, k: &K) -> Option<&'a V> {
         let key = KeyRef { k: k };
-        match self.map.get(&key) {
-            None => None,
+        let idx = match self.map.get(&key) {
+            None => return None,
+            Some(idx) => *idx,
+        };
+
+        if self.is_expired(idx) {
+            self.map.remove(&key);
+            self.counts[idx] = 0;
+            return None;
+        }
+
+        self.mark_used(idx);
+        self.entries.get(idx).map(|entry| &entry.val)
+    }
+
+    /// Return a mutable reference to the value corresponding to the key in the cache or
+    /// `None` if it is not present in the cache. Marks the key's entry as used if it exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// cache.put(1, "a".to_string());
+    /// *cache.get_mut(&1).unwrap() += "b";
+    /// assert_eq!(cache.get(&1), Some(&"ab".to_string()));
+    /// ```
+    pub fn get_mut<'a>(&'a mut self, k: &K) -> Option<&'a mut V> {
+        let key = KeyRef { k: k };
+        let idx = match self.map.get(&key) {
+            None => return None,
+            Some(idx) => *idx,
+        };
+
+        if self.is_expired(idx) {
+            self.map.remove(&key);
+            self.counts[idx] = 0;
+            return None;
+        }
+
+        self.mark_used(idx);
+        self.entries.get_mut(idx).map(|entry| &mut entry.val)
+    }
+
+    /// Return a mutable reference to the value corresponding to the key in the cache,
+    /// marking it used if it is already present, or insert the value produced by `f` and
+    /// return a mutable reference to it. Avoids the double lookup of checking `contains`
+    /// and then following up with `put` and `get`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not already present and the value produced by `f`, as weighed by
+    /// the cache's `WeightScale`, exceeds the cache's total capacity. Use `put_with_weight`
+    /// instead, which reports this as an `Err`, if that weight isn't known to fit ahead of
+    /// time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// *cache.get_or_insert_with(1, || "a".to_string()) += "!";
+    /// assert_eq!(cache.get_or_insert_with(1, || "b".to_string()), "a!");
+    /// ```
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, k: K, f: F) -> &mut V {
+        let live_idx = match self.map.get(&KeyRef { k: &k }) {
+            Some(&idx) if !self.is_expired(idx) => Some(idx),
+            _ => None,
+        };
+
+        let idx = match live_idx {
             Some(idx) => {
-                self.bits.set(*idx, true);
-                Some(self.entries.get(*idx).map(|entry| &entry.val).unwrap())
+                self.mark_used(idx);
+                idx
             }
-        }
+            None => {
+                let v = f();
+                let ttl = self.ttl;
+                let weight = self.scale.weight(&k, &v);
+                match self.put_entry(k, v, ttl, weight) {
+                    // `put_entry` already seeds a freshly-inserted entry's reference count;
+                    // marking it used again here would double-count relative to `put`
+                    Ok(idx) => idx,
+                    Err(_) => panic!("get_or_insert_with: value's weight exceeds cache capacity"),
+                }
+            }
+        };
+
+        &mut self.entries.get_mut(idx).unwrap().val
     }
 
     /// Return the value corresponding to the key in the cache or `None` if it is not
-    /// present in the cache. Unlike `get`, `peek` does not update the key's usage bit.
+    /// present in the cache. Unlike `get`, `peek` does not mark the entry as used.
     ///
     /// # Example
     ///
@@ -217,14 +588,22 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     /// ```
     pub fn peek<'a>(&'a mut self, k: &K) -> Option<&'a V> {
         let key = KeyRef { k: k };
-        match self.map.get(&key) {
-            None => None,
-            Some(idx) => Some(self.entries.get(*idx).map(|entry| &entry.val).unwrap()),
+        let idx = match self.map.get(&key) {
+            None => return None,
+            Some(idx) => *idx,
+        };
+
+        if self.is_expired(idx) {
+            self.map.remove(&key);
+            self.counts[idx] = 0;
+            return None;
         }
+
+        self.entries.get(idx).map(|entry| &entry.val)
     }
 
-    /// Return a bool indicating whether the given key is in the cache. Does not update the
-    /// key's usage bit.
+    /// Return a bool indicating whether the given key is in the cache. Does not mark the
+    /// entry as used.
     ///
     /// # Example
     ///
@@ -242,11 +621,111 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     /// ```
     pub fn contains(&self, k: &K) -> bool {
         let key = KeyRef { k: k };
-        self.map.contains_key(&key)
+        match self.map.get(&key) {
+            None => false,
+            Some(idx) => !self.is_expired(*idx),
+        }
     }
 
-    /// Remove a key from the cache and return a boolean indicating whether the key was in the
-    /// cache or not.
+    /// Return an iterator over the key-value pairs currently in the cache, in an unspecified
+    /// but stable order. Skips expired and freed slots. Unlike `get`, iterating does not mark
+    /// any entry as used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut pairs: Vec<_> = cache.iter().collect();
+    /// pairs.sort();
+    /// assert_eq!(pairs, vec![(&1, &"a"), (&2, &"b")]);
+    /// ```
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a V)> {
+        let mut keep = vec![false; self.entries.len()];
+        for &idx in self.map.values() {
+            if !self.is_expired(idx) {
+                keep[idx] = true;
+            }
+        }
+
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |&(idx, _)| keep[idx])
+            .map(|(_, entry)| (&*entry.key, &entry.val))
+    }
+
+    /// Return an iterator over mutable references to the key-value pairs currently in the
+    /// cache, in an unspecified but stable order. Skips expired and freed slots. Unlike
+    /// `get_mut`, iterating does not mark any entry as used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// cache.put(1, "a".to_string());
+    /// for (_, v) in cache.iter_mut() {
+    ///     v.push('!');
+    /// }
+    /// assert_eq!(cache.get(&1), Some(&"a!".to_string()));
+    /// ```
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a K, &'a mut V)> {
+        let mut keep = vec![false; self.entries.len()];
+        for &idx in self.map.values() {
+            if !self.is_expired(idx) {
+                keep[idx] = true;
+            }
+        }
+
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .filter(move |&(idx, _)| keep[idx])
+            .map(|(_, entry)| (&*entry.key, &mut entry.val))
+    }
+
+    /// Remove all expired entries from the cache, freeing their slots for immediate reuse.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::thread::sleep;
+    /// use std::time::Duration;
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::with_expiry_duration(2, Duration::from_millis(1));
+    ///
+    /// cache.put(1, "a");
+    /// sleep(Duration::from_millis(5));
+    /// cache.remove_expired();
+    /// assert_eq!(cache.len(), 0);
+    /// ```
+    pub fn remove_expired(&mut self) {
+        // walk from the back so each swap-remove only moves an already-checked entry into
+        // the freed slot
+        let expired: Vec<usize> = (0..self.entries.len())
+            .rev()
+            .filter(|&idx| self.is_expired(idx))
+            .collect();
+
+        for idx in expired {
+            let key = KeyRef { k: &*self.entries[idx].key };
+            if self.map.get(&key) == Some(&idx) {
+                self.map.remove(&key);
+            }
+            self.total_weight -= self.entries[idx].weight;
+            self.swap_remove_slot(idx);
+        }
+    }
+
+    /// Remove a key from the cache, returning its value if the key was present. Like `put`'s
+    /// internal eviction, this frees the entry's slot for reuse immediately, without leaving
+    /// it to be discovered by a later clock sweep.
     ///
     /// # Example
     ///
@@ -256,17 +735,59 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     ///
     /// cache.put(2, "a");
     ///
-    /// assert!(!cache.pop(&1));
-    /// assert!(cache.pop(&2));
-    /// assert!(!cache.pop(&2));
+    /// assert_eq!(cache.remove(&1), None);
+    /// assert_eq!(cache.remove(&2), Some("a"));
+    /// assert_eq!(cache.remove(&2), None);
     /// assert_eq!(cache.len(), 0);
     /// ```
-    pub fn pop(&mut self, k: &K) -> bool {
+    pub fn remove(&mut self, k: &K) -> Option<V> {
         let key = KeyRef { k: k };
-        match self.map.remove(&key) {
-            None => false,
-            Some(_) => true,
+        let idx = match self.map.remove(&key) {
+            None => return None,
+            Some(idx) => idx,
+        };
+
+        self.total_weight -= self.entries[idx].weight;
+        Some(self.swap_remove_slot(idx).val)
+    }
+
+    /// Remove every entry from the cache, returning an iterator of the owned key-value pairs
+    /// that were present (expired and freed slots are dropped without being yielded). The
+    /// cache is empty, with its capacity unchanged, once the iterator is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(2);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    ///
+    /// let mut pairs: Vec<_> = cache.drain().collect();
+    /// pairs.sort();
+    /// assert_eq!(pairs, vec![(1, "a"), (2, "b")]);
+    /// assert_eq!(cache.len(), 0);
+    /// assert_eq!(cache.cap(), 2);
+    /// ```
+    pub fn drain<'a>(&'a mut self) -> impl Iterator<Item = (K, V)> + 'a {
+        let mut keep = vec![false; self.entries.len()];
+        for &idx in self.map.values() {
+            if !self.is_expired(idx) {
+                keep[idx] = true;
+            }
         }
+
+        self.map.clear();
+        self.counts.clear();
+        self.total_weight = 0;
+        self.idx = 0;
+
+        self.entries
+            .drain(..)
+            .enumerate()
+            .filter(move |&(idx, _)| keep[idx])
+            .map(|(_, entry)| (*entry.key, entry.val))
     }
 
     /// Return the number of key-value pairs that are currently in the the cache.
@@ -291,7 +812,8 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
         self.map.len()
     }
 
-    /// Return the maximum number of key-value pairs the cache can hold.
+    /// Return the maximum total weight the cache can hold. With the default `ZeroWeightScale`
+    /// every entry has a weight of `1`, so this is the maximum number of key-value pairs.
     ///
     /// # Example
     ///
@@ -303,11 +825,54 @@ impl<K: Hash + Eq, V> ClockCache<K, V> {
     pub fn cap(&self) -> usize {
         self.cap
     }
+
+    /// Change the capacity of the cache to `cap`.
+    ///
+    /// Growing leaves the clock hand and every existing entry untouched -- `entries` may
+    /// still reallocate to satisfy the reservation, but since each entry's key lives in its
+    /// own heap allocation (see `KeyRef`), that reallocation never invalidates `map`'s
+    /// lookups. Shrinking evicts entries one at a time -- preferring ones whose reference
+    /// count has decayed to zero, advancing the clock hand exactly as `put` does -- until the
+    /// total weight fits within `cap`, then releases the now-excess `entries`/`counts`
+    /// capacity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clock_cache::ClockCache;
+    /// let mut cache = ClockCache::new(3);
+    ///
+    /// cache.put(1, "a");
+    /// cache.put(2, "b");
+    /// cache.put(3, "c");
+    ///
+    /// cache.set_capacity(2);
+    /// assert_eq!(cache.len(), 2);
+    /// assert_eq!(cache.cap(), 2);
+    /// ```
+    pub fn set_capacity(&mut self, cap: usize) {
+        if cap >= self.cap {
+            self.map.reserve(cap - self.cap);
+            self.entries.reserve(cap.saturating_sub(self.entries.len()));
+            self.cap = cap;
+            return;
+        }
+
+        while self.total_weight > cap && !self.entries.is_empty() {
+            self.evict_one();
+        }
+
+        self.cap = cap;
+        self.entries.shrink_to_fit();
+        self.counts.shrink_to_fit();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
+    use std::thread::sleep;
+    use std::time::Duration;
     use super::ClockCache;
 
     fn assert_opt_eq<V: PartialEq + Debug>(opt: Option<&V>, v: V) {
@@ -370,16 +935,275 @@ mod tests {
     }
 
     #[test]
-    fn test_pop() {
+    fn test_remove() {
         let mut cache = ClockCache::new(2);
 
         cache.put("apple", "red");
         cache.put("banana", "yellow");
 
-        assert!(cache.pop(&"apple"));
-        assert!(cache.pop(&"banana"));
-        assert!(!cache.pop(&"apple"));
-        assert!(!cache.pop(&"apple"));
+        assert_eq!(cache.remove(&"apple"), Some("red"));
+        assert_eq!(cache.remove(&"banana"), Some("yellow"));
+        assert_eq!(cache.remove(&"apple"), None);
+        assert_eq!(cache.remove(&"apple"), None);
         assert_eq!(cache.len(), 0);
     }
+
+    #[test]
+    fn test_remove_reclaims_capacity() {
+        let mut cache = ClockCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_eq!(cache.remove(&"apple"), Some("red"));
+        assert_eq!(cache.len(), 1);
+
+        // the slot freed by removing "apple" should be reused rather than evicting the
+        // still-live "banana" entry
+        cache.put("pear", "green");
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+        assert_opt_eq(cache.get(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_with_hasher_uses_given_hash_builder() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        let mut cache: ClockCache<&str, &str, BuildHasherDefault<DefaultHasher>> =
+            ClockCache::with_hasher(2, BuildHasherDefault::default());
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+
+        // eviction should still work as normal on top of the custom hasher
+        cache.put("pear", "green");
+        assert!(cache.get(&"apple").is_none());
+        assert_opt_eq(cache.get(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_put_with_expiry_overrides_default_ttl() {
+        let mut cache = ClockCache::with_expiry_duration(2, Duration::from_secs(60));
+
+        cache.put_with_expiry(1, "a", Duration::from_millis(1));
+        cache.put(2, "b");
+        sleep(Duration::from_millis(5));
+
+        // "a"'s shorter per-entry ttl should win over the cache's 60s default, while "b"
+        // (which used the default) is still live
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&"b"));
+    }
+
+    #[test]
+    fn test_expired_entry_preferred_for_reuse_before_remove_expired() {
+        let mut cache = ClockCache::with_expiry_duration(1, Duration::from_millis(1));
+
+        cache.put(1, "a");
+        sleep(Duration::from_millis(5));
+
+        // an expired entry should be evicted in preference to anything else the clock sweep
+        // passes over, so its slot is available to `put` without an explicit
+        // `remove_expired()` call first
+        cache.put(2, "b");
+
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&"b"));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_contains_treats_expired_entry_as_absent() {
+        let mut cache = ClockCache::with_expiry_duration(2, Duration::from_millis(1));
+
+        cache.put(1, "a");
+        sleep(Duration::from_millis(5));
+
+        assert!(!cache.contains(&1));
+    }
+
+    #[test]
+    fn test_with_max_count_resists_scan() {
+        let mut cache = super::ClockCache::with_max_count(2, 3);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        // bump "apple"'s reference count above what plain CLOCK (max_count == 1) could
+        // reach, so it survives the extra clock sweep that evicts "banana" instead
+        assert_opt_eq(cache.get(&"apple"), "red");
+
+        cache.put("pear", "green");
+
+        assert_opt_eq(cache.get(&"apple"), "red");
+        assert_opt_eq(cache.get(&"pear"), "green");
+        assert!(cache.get(&"banana").is_none());
+    }
+
+    #[test]
+    fn test_with_max_count_zero_does_not_exceed_ceiling() {
+        // a degenerate max_count of 0 should behave like a cache with no scan resistance
+        // at all, not hand out a starting counter above its own configured ceiling
+        let mut cache = super::ClockCache::with_max_count(2, 0);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        cache.put("pear", "green");
+
+        assert_eq!(cache.len(), 2);
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+        assert_opt_eq(cache.get(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_get_or_insert_with_new_entry_does_not_double_count() {
+        let mut cache = super::ClockCache::with_max_count(2, 2);
+
+        // a brand-new entry created through `get_or_insert_with` should end up with the same
+        // starting reference count as one created through `put`, not one bumped an extra
+        // time by also calling `mark_used` on it
+        let _ = cache.get_or_insert_with("apple", || "red");
+        cache.put("banana", "yellow");
+        cache.put("pear", "green");
+
+        assert!(cache.get(&"apple").is_none());
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+        assert_opt_eq(cache.get(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_iter_skips_freed_slots_and_does_not_mark_used() {
+        // max_count 2, so marking "banana" used during iteration would let it outlive "pear"
+        let mut cache = super::ClockCache::with_max_count(2, 2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        cache.remove(&"apple");
+
+        let mut pairs: Vec<_> = cache.iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(&"banana", &"yellow")]);
+
+        // iterating must not mark "banana" as used; a subsequent put should still be able
+        // to evict it
+        cache.put("pear", "green");
+        cache.put("kiwi", "brown");
+        assert!(cache.get(&"banana").is_none());
+    }
+
+    #[test]
+    fn test_iter_mut_updates_values_in_place() {
+        let mut cache = ClockCache::new(2);
+
+        cache.put(1, "a".to_string());
+        cache.put(2, "b".to_string());
+
+        for (_, v) in cache.iter_mut() {
+            v.push('!');
+        }
+
+        assert_opt_eq(cache.get(&1), "a!".to_string());
+        assert_opt_eq(cache.get(&2), "b!".to_string());
+    }
+
+    #[test]
+    fn test_drain_empties_cache_and_yields_live_pairs() {
+        let mut cache = ClockCache::new(2);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+
+        let mut pairs: Vec<_> = cache.drain().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![("apple", "red"), ("banana", "yellow")]);
+
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.cap(), 2);
+
+        cache.put("pear", "green");
+        assert_opt_eq(cache.get(&"pear"), "green");
+    }
+
+    #[test]
+    fn test_remove_fixes_up_swapped_entry() {
+        let mut cache = ClockCache::new(3);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        cache.put("pear", "green");
+
+        // removing the first-inserted entry swaps the last slot into its place; the
+        // swapped-in entry must remain reachable under its own key
+        assert_eq!(cache.remove(&"apple"), Some("red"));
+        assert_opt_eq(cache.get(&"banana"), "yellow");
+        assert_opt_eq(cache.get(&"pear"), "green");
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_set_capacity_shrinks_by_more_than_one_slot() {
+        let mut cache = ClockCache::new(4);
+
+        cache.put("apple", "red");
+        cache.put("banana", "yellow");
+        cache.put("pear", "green");
+        cache.put("kiwi", "brown");
+
+        // shrinking by more than one slot in a single call must not hang: each eviction has
+        // to make forward progress even once it lands on a slot a previous eviction already
+        // reclaimed
+        cache.set_capacity(1);
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.cap(), 1);
+        assert_eq!(cache.weight(), 1);
+    }
+
+    #[test]
+    fn test_put_with_weight_reclaims_every_evicted_slot() {
+        let mut cache = ClockCache::new(4);
+
+        // a single big put that must evict more than one small entry should not leave the
+        // other evicted slots behind as permanent zombies
+        assert!(cache.put_with_weight(1, "a", 1).is_ok());
+        assert!(cache.put_with_weight(2, "b", 1).is_ok());
+        assert!(cache.put_with_weight(3, "c", 1).is_ok());
+        assert!(cache.put_with_weight(4, "d", 4).is_ok());
+
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.len(), 1);
+        assert_opt_eq(cache.get(&4), "d");
+
+        // the reclaimed weight budget should be fully usable again by later small puts,
+        // rather than being eaten by zombie slots left over from the eviction above
+        assert!(cache.put_with_weight(5, "e", 1).is_ok());
+        assert!(cache.put_with_weight(6, "f", 1).is_ok());
+        assert!(cache.put_with_weight(7, "g", 1).is_ok());
+        assert_eq!(cache.weight(), 3);
+
+        assert!(cache.put_with_weight(8, "h", 1).is_ok());
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_put_with_weight_evicts_to_cover_growth_of_existing_key() {
+        let mut cache = ClockCache::new(4);
+
+        // re-putting an existing key with a larger weight must evict other entries to stay
+        // within cap, just like inserting a new key that needs the same weight would
+        assert!(cache.put_with_weight(1, "a", 1).is_ok());
+        assert!(cache.put_with_weight(2, "b", 1).is_ok());
+        assert!(cache.put_with_weight(3, "c", 1).is_ok());
+        assert!(cache.put_with_weight(1, "a2", 4).is_ok());
+
+        assert_eq!(cache.weight(), 4);
+        assert!(cache.weight() <= cache.cap());
+        assert_opt_eq(cache.get(&1), "a2");
+    }
 }